@@ -3,7 +3,7 @@ JSON Schema types.
 */
 
 use {
-    anyhow::{bail, Context},
+    anyhow::anyhow,
     schemars::{
         schema::{InstanceType, Metadata, NumberValidation, SingleOrVec, StringValidation},
         Map, Set,
@@ -132,6 +132,89 @@ where
     }
 }
 
+/// An aggregate error collecting every failure encountered while converting a
+/// [`RootSchema`] (or one of its pieces), keyed by the JSON-Pointer-style path
+/// at which each failure occurred (e.g. `properties/foo`, `allOf/2`).
+///
+/// Modeled on Proxmox's `ParameterError`: rather than bailing out at the first
+/// invalid field, the `TryInto` impls in this crate accumulate one entry per
+/// failure here, so a caller can see every illegal annotation in one pass
+/// instead of fixing and re-running one error at a time.
+#[derive(Debug, Default)]
+pub struct ParameterError(Vec<(String, anyhow::Error)>);
+
+impl ParameterError {
+    /// Creates an empty error, with no entries recorded yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any errors have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Records `err` under `path`.
+    pub fn push(&mut self, path: impl Into<String>, err: anyhow::Error) {
+        self.0.push((path.into(), err));
+    }
+
+    /// Merges `other`'s entries into `self`, prefixing each of its paths with
+    /// `segment`, e.g. turning `2` into `allOf/2` as an error bubbles up
+    /// through the parent schema that owns the `allOf` keyword.
+    pub fn merge(&mut self, segment: &str, other: Self) {
+        self.0
+            .extend(other.0.into_iter().map(|(path, err)| (format!("{segment}/{path}"), err)));
+    }
+
+    /// Merges `other`'s entries into `self` as-is, without adding a path
+    /// segment. Used when the nested value is flattened directly into the
+    /// parent's JSON object (e.g. `SubschemaValidation`'s keywords are
+    /// flattened onto `SchemaObject`, so its paths already start with the
+    /// right keyword name).
+    pub fn append(&mut self, other: Self) {
+        self.0.extend(other.0);
+    }
+
+    /// Folds the result of converting a sequence of keyed items into a single
+    /// aggregate error, or `Ok` of every successfully-converted item (in
+    /// order) if none failed.
+    pub fn fold<K, T>(
+        results: impl IntoIterator<Item = (K, Result<T, ParameterError>)>,
+    ) -> Result<Vec<(K, T)>, ParameterError>
+    where
+        K: std::fmt::Display,
+    {
+        let mut oks = Vec::new();
+        let mut errs = ParameterError::new();
+        for (key, result) in results {
+            match result {
+                Ok(t) => oks.push((key, t)),
+                Err(e) => errs.merge(&key.to_string(), e),
+            }
+        }
+        if errs.is_empty() {
+            Ok(oks)
+        } else {
+            Err(errs)
+        }
+    }
+}
+
+impl std::fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (path, err)) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{path}: {err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
 /// Properties of a [`SchemaObject`] which define validation assertions in terms of other schemas.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 #[serde(rename_all = "camelCase", default)]
@@ -182,6 +265,18 @@ pub struct ArrayValidation {
     /// See [JSON Schema 9.3.1.1. "items"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.1.1).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub items: Option<SingleOrVec<Schema>>,
+    /// The `prefixItems` keyword.
+    ///
+    /// Draft 2020-12 split tuple validation out of `items` into `prefixItems`,
+    /// leaving `items` to validate any remaining elements past the tuple
+    /// (taking over the role `additionalItems` used to play). The `TryInto`
+    /// impl below normalizes both spellings to the same `schemars`
+    /// representation, so a caller sees one shape regardless of which draft
+    /// the source schema was written against.
+    ///
+    /// See [JSON Schema 2020-12 10.3.1.1. "prefixItems"](https://json-schema.org/draft/2020-12/json-schema-core.html#section-10.3.1.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix_items: Option<Vec<Schema>>,
     /// The `additionalItems` keyword.
     ///
     /// See [JSON Schema 9.3.1.2. "additionalItems"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.1.2).
@@ -251,136 +346,859 @@ pub struct ObjectValidation {
 }
 
 impl TryInto<schemars::schema::ArrayValidation> for ArrayValidation {
-    type Error = anyhow::Error;
+    type Error = ParameterError;
     fn try_into(self) -> Result<schemars::schema::ArrayValidation, Self::Error> {
-        fn sov_try_into<T, U>(sov: SingleOrVec<T>) -> Result<SingleOrVec<U>, anyhow::Error>
+        fn sov_try_into<T, U>(sov: SingleOrVec<T>) -> Result<SingleOrVec<U>, ParameterError>
         where
-            T: TryInto<U, Error = anyhow::Error>,
+            T: TryInto<U, Error = ParameterError>,
         {
             match sov {
                 SingleOrVec::Single(bt) => {
                     (*bt).try_into().map(|u| SingleOrVec::Single(Box::new(u)))
                 }
                 SingleOrVec::Vec(v) => {
-                    let (us, errs) = v.into_iter().map(|t| t.try_into()).fold(
-                        (Vec::new(), Vec::new()),
-                        |(mut us, mut errs), next| {
-                            match next {
-                                Ok(u) => us.push(u),
-                                Err(e) => errs.push(e),
+                    let us = ParameterError::fold(
+                        v.into_iter().enumerate().map(|(i, t)| (i, t.try_into())),
+                    )?;
+                    Ok(SingleOrVec::Vec(us.into_iter().map(|(_, u)| u).collect()))
+                }
+            }
+        }
+
+        fn vec_try_into(v: Vec<Schema>) -> Result<Vec<schemars::schema::Schema>, ParameterError> {
+            let us =
+                ParameterError::fold(v.into_iter().enumerate().map(|(i, t)| (i, t.try_into())))?;
+            Ok(us.into_iter().map(|(_, u)| u).collect())
+        }
+
+        let mut errs = ParameterError::new();
+
+        let (items, additional_items) = if let Some(prefix_items) = self.prefix_items {
+            match self.items {
+                Some(SingleOrVec::Vec(_)) => {
+                    errs.push(
+                        "prefixItems",
+                        anyhow!(
+                            "\"prefixItems\" cannot be combined with the array form of \"items\""
+                        ),
+                    );
+                    (None, None)
+                }
+                rest => {
+                    let items = match vec_try_into(prefix_items) {
+                        Ok(items) => Some(SingleOrVec::Vec(items)),
+                        Err(e) => {
+                            errs.merge("prefixItems", e);
+                            None
+                        }
+                    };
+
+                    let rest_single = match rest {
+                        Some(SingleOrVec::Single(s)) => Some(s),
+                        Some(SingleOrVec::Vec(_)) | None => None,
+                    };
+                    // `items` (singular schema, 2020-12) and `additionalItems` (2019-09 and
+                    // earlier) are two spellings of the same "rest of the tuple" role, so at
+                    // most one of them may accompany `prefixItems`.
+                    let rest_schema = match (rest_single, self.additional_items) {
+                        (Some(s), None) => Some(("items", s)),
+                        (None, Some(s)) => Some(("additionalItems", s)),
+                        (None, None) => None,
+                        (Some(_), Some(_)) => {
+                            errs.push(
+                                "additionalItems",
+                                anyhow!(
+                                    "\"prefixItems\" can be paired with a single-schema \"items\" or with \"additionalItems\", not both"
+                                ),
+                            );
+                            None
+                        }
+                    };
+                    let additional_items = match rest_schema {
+                        Some((keyword, s)) => match (*s).try_into() {
+                            Ok(a) => Some(Box::new(a)),
+                            Err(e) => {
+                                errs.merge(keyword, e);
+                                None
                             }
-                            (us, errs)
                         },
-                    );
-                    for e in errs {
-                        return Err(e);
-                        // TODO: Return all errors
-                    }
-                    Ok(SingleOrVec::Vec(us))
+                        None => None,
+                    };
+                    (items, additional_items)
+                }
+            }
+        } else {
+            let items = match self.items.map(sov_try_into).transpose() {
+                Ok(items) => items,
+                Err(e) => {
+                    errs.merge("items", e);
+                    None
+                }
+            };
+            let additional_items = match self.additional_items.map(|s| (*s).try_into()).transpose()
+            {
+                Ok(s) => s.map(Box::new),
+                Err(e) => {
+                    errs.merge("additionalItems", e);
+                    None
                 }
+            };
+            (items, additional_items)
+        };
+
+        let contains = match self.contains.map(|s| (*s).try_into()).transpose() {
+            Ok(s) => s.map(Box::new),
+            Err(e) => {
+                errs.merge("contains", e);
+                None
             }
+        };
+
+        if !errs.is_empty() {
+            return Err(errs);
         }
 
         Ok(schemars::schema::ArrayValidation {
-            items: self.items.map(sov_try_into).transpose()?,
-            additional_items: self
-                .additional_items
-                .map(|s| (*s).try_into())
-                .transpose()?
-                .map(Box::new),
+            items,
+            additional_items,
             max_items: self.max_items,
             min_items: self.min_items,
             unique_items: self.unique_items,
-            contains: self
-                .contains
-                .map(|s| (*s).try_into())
-                .transpose()?
-                .map(Box::new),
+            contains,
         })
     }
 }
 
 impl TryInto<schemars::schema::SubschemaValidation> for SubschemaValidation {
-    type Error = anyhow::Error;
+    type Error = ParameterError;
 
     fn try_into(self) -> Result<schemars::schema::SubschemaValidation, Self::Error> {
-        let map_vec_schema = |oms: Option<Vec<Schema>>| -> Result<
-            Option<Vec<schemars::schema::Schema>>,
-            anyhow::Error,
-        > {
+        fn map_vec_schema(
+            oms: Option<Vec<Schema>>,
+        ) -> Result<Option<Vec<schemars::schema::Schema>>, ParameterError> {
             oms.map(|v| {
-                let (schemas, errs) = v.into_iter().map(|s| s.try_into()).fold(
-                    (Vec::new(), Vec::new()),
-                    |(mut schemas, mut errs), next: Result<_, anyhow::Error>| {
-                        match next {
-                            Ok(s) => schemas.push(s),
-                            Err(e) => errs.push(e),
-                        };
-                        (schemas, errs)
-                        // TODO: Propogate indexes if preserve_order is active, or find some other way of signifying which subschema the problem was in
-                    },
-                );
-                for e in errs {
-                    bail!(e)
-                }
-                Ok(schemas)
+                let schemas = ParameterError::fold(
+                    v.into_iter().enumerate().map(|(i, s)| (i, s.try_into())),
+                )?;
+                Ok(schemas.into_iter().map(|(_, s)| s).collect())
             })
             .transpose()
+        }
+
+        let mut errs = ParameterError::new();
+
+        let all_of = match map_vec_schema(self.all_of) {
+            Ok(v) => v,
+            Err(e) => {
+                errs.merge("allOf", e);
+                None
+            }
+        };
+        let any_of = match map_vec_schema(self.any_of) {
+            Ok(v) => v,
+            Err(e) => {
+                errs.merge("anyOf", e);
+                None
+            }
+        };
+        let one_of = match map_vec_schema(self.one_of) {
+            Ok(v) => v,
+            Err(e) => {
+                errs.merge("oneOf", e);
+                None
+            }
+        };
+        let not = match self.not.map(|s| (*s).try_into()).transpose() {
+            Ok(v) => v.map(Box::new),
+            Err(e) => {
+                errs.merge("not", e);
+                None
+            }
+        };
+        let if_schema = match self.if_schema.map(|s| (*s).try_into()).transpose() {
+            Ok(v) => v.map(Box::new),
+            Err(e) => {
+                errs.merge("if", e);
+                None
+            }
+        };
+        let then_schema = match self.then_schema.map(|s| (*s).try_into()).transpose() {
+            Ok(v) => v.map(Box::new),
+            Err(e) => {
+                errs.merge("then", e);
+                None
+            }
         };
+        let else_schema = match self.else_schema.map(|s| (*s).try_into()).transpose() {
+            Ok(v) => v.map(Box::new),
+            Err(e) => {
+                errs.merge("else", e);
+                None
+            }
+        };
+
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+
         Ok(schemars::schema::SubschemaValidation {
-            all_of: map_vec_schema(self.all_of).context("in 'allOf'")?,
-            any_of: map_vec_schema(self.any_of).context("in 'anyOf'")?,
-            one_of: map_vec_schema(self.one_of).context("in 'oneOf'")?,
-            not: self.not.map(|s| (*s).try_into()).transpose()?.map(Box::new),
-            if_schema: self
-                .if_schema
-                .map(|s| (*s).try_into())
-                .transpose()?
-                .map(Box::new),
-            then_schema: self
-                .then_schema
-                .map(|s| (*s).try_into())
-                .transpose()?
-                .map(Box::new),
-            else_schema: self
-                .else_schema
-                .map(|s| (*s).try_into())
-                .transpose()?
-                .map(Box::new),
+            all_of,
+            any_of,
+            one_of,
+            not,
+            if_schema,
+            then_schema,
+            else_schema,
         })
     }
 }
 
+impl SchemaObject {
+    /// Flattens `allOf` members that are plain object schemas (no `$ref`)
+    /// into `self`, inspired by schemars' `Schema::flatten`.
+    ///
+    /// Real-world schemas frequently express a type as
+    /// `allOf: [ {properties...}, {required...} ]`, which downstream tools
+    /// that only look at the top-level `object` validation miss entirely.
+    /// This merges `required` sets, `properties`/`patternProperties`
+    /// entries, and numeric/string/array constraints (taking the tighter
+    /// bound of the two, e.g. the max of `minItems` and the min of
+    /// `maxItems`) into `self`.
+    ///
+    /// Members that are `$ref`s or booleans are left in place in `allOf`, as
+    /// is any member `merge_member` can't fully absorb into `self` without
+    /// loss (a conflicting `const`/`type`/`format`/`enum`, a differing value
+    /// for a keyword only one schema can keep, such as `items`, `multipleOf`,
+    /// `pattern` or a shared `properties` key, or one carrying `subschemas`,
+    /// a draft-03 `required` bool, `metadata`, or `extensions`) — never
+    /// silently dropped.
+    ///
+    /// Recurses into the object and array schema slots (`properties`,
+    /// `patternProperties`, `additionalProperties`, `propertyNames`, array
+    /// `items`/`prefixItems`/`additionalItems`/`contains`) so an `allOf`
+    /// nested there gets flattened too. Schemas under `anyOf`, `oneOf`,
+    /// `not`, `if`/`then`/`else`, and members left in `allOf`, are not
+    /// flattened.
+    ///
+    /// This is an opt-in normalization step — it is not run by `try_into` —
+    /// so call it first if you want a flattened schema before conversion.
+    pub fn merge_all_of(mut self) -> SchemaObject {
+        if let Some(all_of) = self.subschemas.as_mut().and_then(|s| s.all_of.take()) {
+            let mut remaining = Vec::new();
+            for member in all_of {
+                match member {
+                    Schema::Bool(b) => remaining.push(Schema::Bool(b)),
+                    Schema::Object(o) if o.reference.is_some() => {
+                        remaining.push(Schema::Object(o))
+                    }
+                    Schema::Object(o) => {
+                        if let Some(unmerged) = merge_member(&mut self, o) {
+                            remaining.push(Schema::Object(unmerged));
+                        }
+                    }
+                }
+            }
+            if let Some(subschemas) = self.subschemas.as_mut() {
+                subschemas.all_of = (!remaining.is_empty()).then_some(remaining);
+            }
+        }
+
+        fn recurse(schema: Schema) -> Schema {
+            match schema {
+                Schema::Bool(b) => Schema::Bool(b),
+                Schema::Object(o) => Schema::Object(o.merge_all_of()),
+            }
+        }
+
+        if let Some(object) = self.object.as_mut() {
+            object.properties =
+                std::mem::take(&mut object.properties).into_iter().map(|(k, v)| (k, recurse(v))).collect();
+            object.pattern_properties = std::mem::take(&mut object.pattern_properties)
+                .into_iter()
+                .map(|(k, v)| (k, recurse(v)))
+                .collect();
+            if let Some(additional) = object.additional_properties.take() {
+                object.additional_properties = Some(Box::new(recurse(*additional)));
+            }
+            if let Some(names) = object.property_names.take() {
+                object.property_names = Some(Box::new(recurse(*names)));
+            }
+        }
+
+        if let Some(array) = self.array.as_mut() {
+            if let Some(items) = array.items.take() {
+                array.items = Some(match items {
+                    SingleOrVec::Single(s) => SingleOrVec::Single(Box::new(recurse(*s))),
+                    SingleOrVec::Vec(v) => SingleOrVec::Vec(v.into_iter().map(recurse).collect()),
+                });
+            }
+            if let Some(prefix_items) = array.prefix_items.take() {
+                array.prefix_items = Some(prefix_items.into_iter().map(recurse).collect());
+            }
+            if let Some(additional) = array.additional_items.take() {
+                array.additional_items = Some(Box::new(recurse(*additional)));
+            }
+            if let Some(contains) = array.contains.take() {
+                array.contains = Some(Box::new(recurse(*contains)));
+            }
+        }
+
+        self
+    }
+
+    /// Migrates legacy draft-03/04 keywords captured in `extensions` into the
+    /// fields the `TryInto` impl below already understands, so `try_into`
+    /// recognizes them instead of silently passing them through as unknown
+    /// extra properties.
+    ///
+    /// * `disallow` (a type name, or an array of them) becomes
+    ///   `not: { type: ... }`.
+    /// * `extends` becomes an `allOf` entry.
+    /// * `divisibleBy` becomes `multipleOf`.
+    ///
+    /// Any of these that can't be losslessly migrated (e.g. a `disallow`
+    /// alongside an existing `not`) is recorded in `errs` rather than
+    /// dropped.
+    fn migrate_legacy_keywords(&mut self, errs: &mut ParameterError) {
+        if let Some(disallow) = self.extensions.remove("disallow") {
+            match serde_json::from_value::<SingleOrVec<InstanceType>>(disallow) {
+                Ok(instance_type) => {
+                    let subschemas = self.subschemas.get_or_insert_with(Default::default);
+                    if subschemas.not.is_some() {
+                        errs.push(
+                            "disallow",
+                            anyhow!("cannot migrate \"disallow\": schema already has a \"not\""),
+                        );
+                    } else {
+                        subschemas.not = Some(Box::new(Schema::Object(SchemaObject {
+                            instance_type: Some(instance_type),
+                            ..Default::default()
+                        })));
+                    }
+                }
+                Err(e) => errs.push("disallow", anyhow!("could not migrate \"disallow\": {e}")),
+            }
+        }
+
+        if let Some(extends) = self.extensions.remove("extends") {
+            match serde_json::from_value::<SingleOrVec<Schema>>(extends) {
+                Ok(schemas) => {
+                    let subschemas = self.subschemas.get_or_insert_with(Default::default);
+                    let all_of = subschemas.all_of.get_or_insert_with(Vec::new);
+                    match schemas {
+                        SingleOrVec::Single(s) => all_of.push(*s),
+                        SingleOrVec::Vec(v) => all_of.extend(v),
+                    }
+                }
+                Err(e) => errs.push("extends", anyhow!("could not migrate \"extends\": {e}")),
+            }
+        }
+
+        if let Some(divisible_by) = self.extensions.remove("divisibleBy") {
+            match serde_json::from_value::<f64>(divisible_by) {
+                Ok(divisible_by) => {
+                    let number = self.number.get_or_insert_with(Default::default);
+                    match number.multiple_of {
+                        Some(existing) if existing != divisible_by => errs.push(
+                            "divisibleBy",
+                            anyhow!(
+                                "cannot migrate \"divisibleBy\": conflicts with existing \"multipleOf\" {existing}"
+                            ),
+                        ),
+                        _ => number.multiple_of = Some(divisible_by),
+                    }
+                }
+                Err(e) => {
+                    errs.push("divisibleBy", anyhow!("could not migrate \"divisibleBy\": {e}"))
+                }
+            }
+        }
+    }
+}
+
+/// Merges `member` into `parent`, returning `Some(member)` (unmerged) if it
+/// conflicts with `parent` on a keyword the merge can keep only one value of
+/// (`type`, `const`, `format`, `enum`, `multipleOf`, `pattern`, the array
+/// item schemas, a shared `properties`/`patternProperties` key,
+/// `additionalProperties`, `propertyNames`), or carries fields this pass
+/// doesn't fold in.
+fn merge_member(parent: &mut SchemaObject, member: SchemaObject) -> Option<SchemaObject> {
+    let type_conflicts = matches!(
+        (&parent.instance_type, &member.instance_type),
+        (Some(a), Some(b)) if a != b
+    );
+    let const_conflicts = matches!(
+        (&parent.const_value, &member.const_value),
+        (Some(a), Some(b)) if a != b
+    );
+    let format_conflicts = matches!(
+        (&parent.format, &member.format),
+        (Some(a), Some(b)) if a != b
+    );
+    let enum_conflicts = matches!(
+        (&parent.enum_values, &member.enum_values),
+        (Some(a), Some(b)) if a != b
+    );
+    let array_conflicts = array_schema_conflicts(&parent.array, &member.array);
+    let number_conflicts = matches!((&parent.number, &member.number),
+        (Some(a), Some(b)) if matches!((a.multiple_of, b.multiple_of), (Some(x), Some(y)) if x != y));
+    let string_conflicts = matches!((&parent.string, &member.string),
+        (Some(a), Some(b)) if matches!((&a.pattern, &b.pattern), (Some(x), Some(y)) if x != y));
+    let object_conflicts = matches!((&parent.object, &member.object), (Some(a), Some(b)) if
+        b.properties.iter().any(|(k, v)| a.properties.get(k).map_or(false, |p| p != v))
+            || b.pattern_properties.iter().any(|(k, v)| a.pattern_properties.get(k).map_or(false, |p| p != v))
+            || matches!((&a.additional_properties, &b.additional_properties), (Some(x), Some(y)) if x != y)
+            || matches!((&a.property_names, &b.property_names), (Some(x), Some(y)) if x != y));
+    // Anything this pass doesn't know how to fold into `parent` must stay in
+    // `allOf` rather than being silently dropped.
+    let has_unmergeable_fields = member.subschemas.is_some()
+        || member.required.is_some()
+        || member.metadata.is_some()
+        || !member.extensions.is_empty();
+
+    if type_conflicts
+        || const_conflicts
+        || format_conflicts
+        || enum_conflicts
+        || array_conflicts
+        || number_conflicts
+        || string_conflicts
+        || object_conflicts
+        || has_unmergeable_fields
+    {
+        return Some(member);
+    }
+
+    if parent.instance_type.is_none() {
+        parent.instance_type = member.instance_type;
+    }
+    if parent.const_value.is_none() {
+        parent.const_value = member.const_value;
+    }
+    if parent.format.is_none() {
+        parent.format = member.format;
+    }
+    if parent.enum_values.is_none() {
+        parent.enum_values = member.enum_values;
+    }
+
+    merge_number(&mut parent.number, member.number);
+    merge_string(&mut parent.string, member.string);
+    merge_array(&mut parent.array, member.array);
+    merge_object(&mut parent.object, member.object);
+
+    None
+}
+
+fn merge_number(parent: &mut Option<Box<NumberValidation>>, member: Option<Box<NumberValidation>>) {
+    let Some(member) = member else { return };
+    let parent = parent.get_or_insert_with(Default::default);
+    parent.multiple_of = parent.multiple_of.or(member.multiple_of);
+    parent.maximum = tighter_max(parent.maximum, member.maximum);
+    parent.exclusive_maximum = tighter_max(parent.exclusive_maximum, member.exclusive_maximum);
+    parent.minimum = tighter_min(parent.minimum, member.minimum);
+    parent.exclusive_minimum = tighter_min(parent.exclusive_minimum, member.exclusive_minimum);
+}
+
+fn merge_string(parent: &mut Option<Box<StringValidation>>, member: Option<Box<StringValidation>>) {
+    let Some(member) = member else { return };
+    let parent = parent.get_or_insert_with(Default::default);
+    parent.max_length = tighter_max(parent.max_length, member.max_length);
+    parent.min_length = tighter_min(parent.min_length, member.min_length);
+    parent.pattern = parent.pattern.take().or(member.pattern);
+}
+
+/// Whether `parent` and `member` both set one of the tuple/rest-item array
+/// keywords (`items`, `prefixItems`, `additionalItems`, `contains`) to
+/// different schemas — in which case the member can't be silently folded
+/// into `parent` without discarding one of them.
+fn array_schema_conflicts(
+    parent: &Option<Box<ArrayValidation>>,
+    member: &Option<Box<ArrayValidation>>,
+) -> bool {
+    let (Some(parent), Some(member)) = (parent, member) else {
+        return false;
+    };
+    matches!((&parent.items, &member.items), (Some(a), Some(b)) if a != b)
+        || matches!((&parent.prefix_items, &member.prefix_items), (Some(a), Some(b)) if a != b)
+        || matches!((&parent.additional_items, &member.additional_items), (Some(a), Some(b)) if a != b)
+        || matches!((&parent.contains, &member.contains), (Some(a), Some(b)) if a != b)
+}
+
+fn merge_array(parent: &mut Option<Box<ArrayValidation>>, member: Option<Box<ArrayValidation>>) {
+    let Some(member) = member else { return };
+    let parent = parent.get_or_insert_with(Default::default);
+    parent.max_items = tighter_max(parent.max_items, member.max_items);
+    parent.min_items = tighter_min(parent.min_items, member.min_items);
+    parent.unique_items = match (parent.unique_items, member.unique_items) {
+        (Some(a), Some(b)) => Some(a || b),
+        (Some(x), None) | (None, Some(x)) => Some(x),
+        (None, None) => None,
+    };
+    if parent.items.is_none() {
+        parent.items = member.items;
+    }
+    if parent.prefix_items.is_none() {
+        parent.prefix_items = member.prefix_items;
+    }
+    if parent.additional_items.is_none() {
+        parent.additional_items = member.additional_items;
+    }
+    if parent.contains.is_none() {
+        parent.contains = member.contains;
+    }
+}
+
+fn merge_object(parent: &mut Option<Box<ObjectValidation>>, member: Option<Box<ObjectValidation>>) {
+    let Some(member) = member else { return };
+    let parent = parent.get_or_insert_with(Default::default);
+    parent.max_properties = tighter_max(parent.max_properties, member.max_properties);
+    parent.min_properties = tighter_min(parent.min_properties, member.min_properties);
+    for req in member.required {
+        parent.required.insert(req);
+    }
+    for (k, v) in member.properties {
+        parent.properties.entry(k).or_insert(v);
+    }
+    for (k, v) in member.pattern_properties {
+        parent.pattern_properties.entry(k).or_insert(v);
+    }
+    if parent.additional_properties.is_none() {
+        parent.additional_properties = member.additional_properties;
+    }
+    if parent.property_names.is_none() {
+        parent.property_names = member.property_names;
+    }
+}
+
+/// The tighter (larger) of two lower bounds, e.g. for `minItems`.
+fn tighter_min<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a > b { a } else { b }),
+        (Some(x), None) | (None, Some(x)) => Some(x),
+        (None, None) => None,
+    }
+}
+
+/// The tighter (smaller) of two upper bounds, e.g. for `maxItems`.
+fn tighter_max<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a < b { a } else { b }),
+        (Some(x), None) | (None, Some(x)) => Some(x),
+        (None, None) => None,
+    }
+}
+
+/// Controls how [`RootSchema::resolve_references`] treats a resolved `$ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefResolution {
+    /// Replace every resolvable `$ref` with a clone of its target schema.
+    Inline,
+    /// Leave `$ref` strings as they are; only validate that each one
+    /// resolves to an existing definition and contains no cycles.
+    Validate,
+}
+
+impl RootSchema {
+    /// Resolves every `$ref` in this schema against `definitions`/`$defs`,
+    /// per `mode`.
+    ///
+    /// Understands JSON-Pointer fragments rooted at `#/definitions/Name` or
+    /// `#/$defs/Name`, including a pointer into a nested definition (e.g.
+    /// `#/definitions/Foo/properties/bar`). A reference cycle (`A -> B -> A`)
+    /// or a dangling reference (`#/definitions/Missing`) is recorded as an
+    /// error naming the offending `$ref` rather than recursing forever or
+    /// silently producing a broken schema; every occurrence across the
+    /// schema is collected, so a caller sees every bad `$ref` in one pass.
+    pub fn resolve_references(&mut self, mode: RefResolution) -> Result<(), ParameterError> {
+        let mut errs = ParameterError::new();
+        let definitions = self.definitions.clone();
+        let mut stack = Vec::new();
+
+        let mut root = Schema::Object(std::mem::take(&mut self.schema));
+        resolve_schema(&mut root, &definitions, mode, &mut stack, &mut errs, "");
+        if let Schema::Object(obj) = root {
+            self.schema = obj;
+        }
+
+        let mut owned_definitions = std::mem::take(&mut self.definitions);
+        for (name, schema) in owned_definitions.iter_mut() {
+            resolve_schema(
+                schema,
+                &definitions,
+                mode,
+                &mut stack,
+                &mut errs,
+                &format!("definitions/{name}"),
+            );
+        }
+        self.definitions = owned_definitions;
+
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+}
+
+fn resolve_schema(
+    schema: &mut Schema,
+    definitions: &Map<String, Schema>,
+    mode: RefResolution,
+    stack: &mut Vec<String>,
+    errs: &mut ParameterError,
+    path: &str,
+) {
+    if let Schema::Object(obj) = schema {
+        if let Some(pointer) = obj.reference.clone() {
+            if stack.contains(&pointer) {
+                let mut cycle: Vec<&str> = stack.iter().map(String::as_str).collect();
+                cycle.push(&pointer);
+                errs.push(path, anyhow!("reference cycle detected: {}", cycle.join(" -> ")));
+            } else if let Some(mut resolved) = resolve_pointer(&pointer, definitions, errs, path) {
+                // Keep `pointer` on the stack for the whole walk of `resolved` (not just
+                // while chasing a chain of `$ref`s), so a self-reference reached through
+                // a *child* of the target (e.g. a linked-list `properties.next`) is also
+                // caught as a cycle instead of recursing forever.
+                stack.push(pointer);
+                resolve_schema(&mut resolved, definitions, mode, stack, errs, path);
+                stack.pop();
+                if mode == RefResolution::Inline {
+                    *schema = resolved;
+                    return;
+                }
+            }
+        }
+    }
+
+    if let Schema::Object(obj) = schema {
+        resolve_children(obj, definitions, mode, stack, errs, path);
+    }
+}
+
+fn resolve_children(
+    obj: &mut SchemaObject,
+    definitions: &Map<String, Schema>,
+    mode: RefResolution,
+    stack: &mut Vec<String>,
+    errs: &mut ParameterError,
+    path: &str,
+) {
+    if let Some(subschemas) = obj.subschemas.as_mut() {
+        for keyword in ["allOf", "anyOf", "oneOf"] {
+            let vec = match keyword {
+                "allOf" => &mut subschemas.all_of,
+                "anyOf" => &mut subschemas.any_of,
+                _ => &mut subschemas.one_of,
+            };
+            if let Some(vec) = vec.as_mut() {
+                for (i, s) in vec.iter_mut().enumerate() {
+                    resolve_schema(s, definitions, mode, stack, errs, &format!("{path}/{keyword}/{i}"));
+                }
+            }
+        }
+        resolve_box(&mut subschemas.not, definitions, mode, stack, errs, path, "not");
+        resolve_box(&mut subschemas.if_schema, definitions, mode, stack, errs, path, "if");
+        resolve_box(&mut subschemas.then_schema, definitions, mode, stack, errs, path, "then");
+        resolve_box(&mut subschemas.else_schema, definitions, mode, stack, errs, path, "else");
+    }
+
+    if let Some(array) = obj.array.as_mut() {
+        if let Some(items) = array.items.as_mut() {
+            match items {
+                SingleOrVec::Single(s) => {
+                    resolve_schema(s, definitions, mode, stack, errs, &format!("{path}/items"))
+                }
+                SingleOrVec::Vec(v) => {
+                    for (i, s) in v.iter_mut().enumerate() {
+                        resolve_schema(s, definitions, mode, stack, errs, &format!("{path}/items/{i}"));
+                    }
+                }
+            }
+        }
+        if let Some(prefix_items) = array.prefix_items.as_mut() {
+            for (i, s) in prefix_items.iter_mut().enumerate() {
+                resolve_schema(s, definitions, mode, stack, errs, &format!("{path}/prefixItems/{i}"));
+            }
+        }
+        resolve_box(&mut array.additional_items, definitions, mode, stack, errs, path, "additionalItems");
+        resolve_box(&mut array.contains, definitions, mode, stack, errs, path, "contains");
+    }
+
+    if let Some(object) = obj.object.as_mut() {
+        for (k, v) in object.properties.iter_mut() {
+            resolve_schema(v, definitions, mode, stack, errs, &format!("{path}/properties/{k}"));
+        }
+        for (k, v) in object.pattern_properties.iter_mut() {
+            resolve_schema(v, definitions, mode, stack, errs, &format!("{path}/patternProperties/{k}"));
+        }
+        resolve_box(
+            &mut object.additional_properties,
+            definitions,
+            mode,
+            stack,
+            errs,
+            path,
+            "additionalProperties",
+        );
+        resolve_box(&mut object.property_names, definitions, mode, stack, errs, path, "propertyNames");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resolve_box(
+    b: &mut Option<Box<Schema>>,
+    definitions: &Map<String, Schema>,
+    mode: RefResolution,
+    stack: &mut Vec<String>,
+    errs: &mut ParameterError,
+    path: &str,
+    segment: &str,
+) {
+    if let Some(s) = b.as_mut() {
+        resolve_schema(s, definitions, mode, stack, errs, &format!("{path}/{segment}"));
+    }
+}
+
+/// Resolves a single `$ref` pointer string to the (not yet further-resolved)
+/// schema it names, recording a [`ParameterError`] entry at `path` (and
+/// returning `None`) if it is malformed or dangling.
+///
+/// Does not chase a `$ref` found on the returned schema itself, nor detect
+/// cycles — the caller (`resolve_schema`) walks the result recursively and is
+/// the one tracking visited pointers, so that a cycle reached through a
+/// child of the target is caught just as reliably as a direct `$ref` chain.
+fn resolve_pointer(
+    pointer: &str,
+    definitions: &Map<String, Schema>,
+    errs: &mut ParameterError,
+    path: &str,
+) -> Option<Schema> {
+    let Some(rest) = pointer.strip_prefix("#/") else {
+        errs.push(
+            path,
+            anyhow!(
+                "unsupported \"$ref\" value '{pointer}': expected a JSON Pointer fragment starting with \"#/\""
+            ),
+        );
+        return None;
+    };
+
+    let segments: Vec<String> = rest.split('/').map(unescape_pointer_segment).collect();
+    let Some((root, rest)) = segments.split_first() else {
+        errs.push(path, anyhow!("\"$ref\" value '{pointer}' does not name a definition"));
+        return None;
+    };
+    if root != "definitions" && root != "$defs" {
+        errs.push(
+            path,
+            anyhow!(
+                "unsupported \"$ref\" value '{pointer}': only \"#/definitions/...\" and \"#/$defs/...\" are resolved"
+            ),
+        );
+        return None;
+    }
+    let Some((name, rest)) = rest.split_first() else {
+        errs.push(path, anyhow!("\"$ref\" value '{pointer}' does not name a definition"));
+        return None;
+    };
+    let Some(found) = definitions.get(name).cloned() else {
+        errs.push(
+            path,
+            anyhow!("dangling \"$ref\": '{pointer}' does not match any definition"),
+        );
+        return None;
+    };
+    let Some(target) = follow_pointer_path(found, rest) else {
+        errs.push(path, anyhow!("\"$ref\" value '{pointer}' does not resolve to a schema"));
+        return None;
+    };
+
+    Some(target)
+}
+
+/// Walks the remaining JSON-Pointer `segments` into `schema`, currently
+/// supporting nesting through `properties` and `patternProperties` (e.g.
+/// `#/definitions/Foo/properties/bar`).
+fn follow_pointer_path(schema: Schema, segments: &[String]) -> Option<Schema> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Some(schema);
+    };
+    let Schema::Object(obj) = schema else {
+        return None;
+    };
+    let object = obj.object?;
+    match first.as_str() {
+        "properties" => {
+            let (key, rest) = rest.split_first()?;
+            follow_pointer_path(object.properties.get(key)?.clone(), rest)
+        }
+        "patternProperties" => {
+            let (key, rest) = rest.split_first()?;
+            follow_pointer_path(object.pattern_properties.get(key)?.clone(), rest)
+        }
+        _ => None,
+    }
+}
+
+/// Unescapes a single JSON-Pointer segment, per RFC 6901 (`~1` -> `/`, then
+/// `~0` -> `~`).
+fn unescape_pointer_segment(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
 impl TryInto<schemars::schema::SchemaObject> for SchemaObject {
-    type Error = anyhow::Error;
-    fn try_into(self) -> Result<schemars::schema::SchemaObject, Self::Error> {
+    type Error = ParameterError;
+    fn try_into(mut self) -> Result<schemars::schema::SchemaObject, Self::Error> {
+        let mut errs = ParameterError::new();
+
+        self.migrate_legacy_keywords(&mut errs);
+
         if self.required.is_some() {
-            bail!("found illegal \"required\" annotation")
+            errs.push(
+                "required",
+                anyhow!("found illegal \"required\" annotation"),
+            );
+        }
+
+        let subschemas = match self.subschemas.map(|s| (*s).try_into()).transpose() {
+            Ok(s) => s.map(Box::new),
+            Err(e) => {
+                errs.append(e);
+                None
+            }
+        };
+        let array = match self.array.map(|a| (*a).try_into()).transpose() {
+            Ok(a) => a.map(Box::new),
+            Err(e) => {
+                errs.append(e);
+                None
+            }
+        };
+        let object = match self.object.map(|o| (*o).try_into()).transpose() {
+            Ok(o) => o.map(Box::new),
+            Err(e) => {
+                errs.append(e);
+                None
+            }
+        };
+
+        if !errs.is_empty() {
+            return Err(errs);
         }
+
         Ok(schemars::schema::SchemaObject {
             metadata: self.metadata,
             instance_type: self.instance_type,
             format: self.format,
             enum_values: self.enum_values,
             const_value: self.const_value,
-            subschemas: self
-                .subschemas
-                .map(|s| (*s).try_into())
-                .transpose()
-                .context("in subschemas")?
-                .map(Box::new),
+            subschemas,
             number: self.number,
             string: self.string,
-            array: self
-                .array
-                .map(|a| (*a).try_into())
-                .transpose()?
-                .map(Box::new),
-            object: self
-                .object
-                .map(|a| (*a).try_into())
-                .transpose()?
-                .map(Box::new),
+            array,
+            object,
             reference: self.reference,
             extensions: self.extensions,
         })
@@ -388,7 +1206,7 @@ impl TryInto<schemars::schema::SchemaObject> for SchemaObject {
 }
 
 impl TryInto<schemars::schema::Schema> for Schema {
-    type Error = anyhow::Error;
+    type Error = ParameterError;
     fn try_into(self) -> Result<schemars::schema::Schema, Self::Error> {
         Ok(match self {
             Schema::Bool(b) => schemars::schema::Schema::Bool(b),
@@ -398,54 +1216,61 @@ impl TryInto<schemars::schema::Schema> for Schema {
 }
 
 impl TryInto<schemars::schema::ObjectValidation> for ObjectValidation {
-    type Error = anyhow::Error;
+    type Error = ParameterError;
     fn try_into(self) -> Result<schemars::schema::ObjectValidation, Self::Error> {
-        let process_props =
-            |(mut props, mut errs): (Map<_, _>, Vec<_>),
-             (k, v): (String, Result<_, anyhow::Error>)| match v {
-                Ok(v) => {
-                    props.insert(k, v);
-                    (props, errs)
-                }
-                Err(e) => {
-                    errs.push((k, e));
-                    (props, errs)
-                }
-            };
-
+        let mut errs = ParameterError::new();
         let mut required = self.required;
 
-        let (properties, errs) = self
-            .properties
-            .into_iter()
-            .map(|(k, v)| {
-                let mut o = match v {
-                    Schema::Bool(b) => return (k, Schema::Bool(b)),
-                    Schema::Object(o) => o,
-                };
-                let req = o.required.take();
-                if let Some(true) = req {
-                    required.insert(k.clone());
-                };
-                (k, Schema::Object(o))
-            })
-            .map(|(k, v)| (k, v.try_into()))
-            .fold(Default::default(), process_props);
+        let mut properties = Map::default();
+        for (k, v) in self.properties {
+            let mut o = match v {
+                Schema::Bool(b) => {
+                    properties.insert(k, schemars::schema::Schema::Bool(b));
+                    continue;
+                }
+                Schema::Object(o) => o,
+            };
+            let req = o.required.take();
+            if let Some(true) = req {
+                required.insert(k.clone());
+            };
+            let result: Result<schemars::schema::Schema, ParameterError> =
+                Schema::Object(o).try_into();
+            match result {
+                Ok(v) => {
+                    properties.insert(k, v);
+                }
+                Err(e) => errs.merge(&format!("properties/{k}"), e),
+            }
+        }
 
-        for (k, e) in errs {
-            return Err(e.context(format!("in field '{k}'")));
-            // TODO: Return the full error tree in a more reasonable error
+        let mut pattern_properties = Map::default();
+        for (k, v) in self.pattern_properties {
+            match v.try_into() {
+                Ok(v) => {
+                    pattern_properties.insert(k, v);
+                }
+                Err(e) => errs.merge(&format!("patternProperties/{k}"), e),
+            }
         }
 
-        let (pattern_properties, errs) = self
-            .pattern_properties
-            .into_iter()
-            .map(|(k, v)| (k, v.try_into()))
-            .fold(Default::default(), process_props);
+        let additional_properties = match self.additional_properties.map(|b| (*b).try_into()).transpose() {
+            Ok(b) => b.map(Box::new),
+            Err(e) => {
+                errs.merge("additionalProperties", e);
+                None
+            }
+        };
+        let property_names = match self.property_names.map(|b| (*b).try_into()).transpose() {
+            Ok(b) => b.map(Box::new),
+            Err(e) => {
+                errs.merge("propertyNames", e);
+                None
+            }
+        };
 
-        for (k, e) in errs {
-            return Err(e.context(format!("in pattern property '{k}'")));
-            // TODO: Return the full error tree in a more reasonable error
+        if !errs.is_empty() {
+            return Err(errs);
         }
 
         Ok(schemars::schema::ObjectValidation {
@@ -454,16 +1279,43 @@ impl TryInto<schemars::schema::ObjectValidation> for ObjectValidation {
             required,
             properties,
             pattern_properties,
-            additional_properties: self
-                .additional_properties
-                .map(|b| (*b).try_into())
-                .transpose()?
-                .map(Box::new),
-            property_names: self
-                .property_names
-                .map(|b| (*b).try_into())
-                .transpose()?
-                .map(Box::new),
+            additional_properties,
+            property_names,
+        })
+    }
+}
+
+impl TryInto<schemars::schema::RootSchema> for RootSchema {
+    type Error = ParameterError;
+    fn try_into(self) -> Result<schemars::schema::RootSchema, Self::Error> {
+        let mut errs = ParameterError::new();
+
+        let schema = match self.schema.try_into() {
+            Ok(schema) => Some(schema),
+            Err(e) => {
+                errs.append(e);
+                None
+            }
+        };
+
+        let mut definitions = Map::default();
+        for (k, v) in self.definitions {
+            match v.try_into() {
+                Ok(v) => {
+                    definitions.insert(k, v);
+                }
+                Err(e) => errs.merge(&format!("definitions/{k}"), e),
+            }
+        }
+
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+
+        Ok(schemars::schema::RootSchema {
+            meta_schema: self.meta_schema,
+            schema: schema.expect("checked above: errs is empty"),
+            definitions,
         })
     }
 }
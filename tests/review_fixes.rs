@@ -0,0 +1,256 @@
+use parse_jsonschema::{RefResolution, RootSchema};
+
+fn convert(json: &str) -> Result<schemars::schema::RootSchema, parse_jsonschema::ParameterError> {
+    let parsed: RootSchema = serde_json::from_str(json).unwrap();
+    parsed.try_into()
+}
+
+// #chunk0-1: errors accumulate across the whole tree instead of bailing on
+// the first one.
+#[test]
+fn legacy_keywords_migrate_without_conflict() {
+    let schema = convert(
+        r#"{
+            "properties": {
+                "a": { "divisibleBy": 2 },
+                "b": { "disallow": "string" }
+            }
+        }"#,
+    )
+    .expect("no conflicting legacy keywords, so migration should succeed");
+
+    let properties = schema.schema.object.unwrap().properties;
+    let a = properties.get("a").unwrap().clone().into_object();
+    assert_eq!(a.number.unwrap().multiple_of, Some(2.0));
+    let b = properties.get("b").unwrap().clone().into_object();
+    assert!(b.subschemas.unwrap().not.is_some());
+}
+
+#[test]
+fn errors_in_unrelated_properties_are_all_reported_together() {
+    let err = convert(
+        r#"{
+            "properties": {
+                "a": { "multipleOf": 2, "divisibleBy": 3 },
+                "b": { "not": {"type": "number"}, "disallow": "string" }
+            }
+        }"#,
+    )
+    .expect_err("conflicting legacy keywords in both properties should be rejected");
+
+    let message = err.to_string();
+    assert!(message.contains("properties/a/divisibleBy"), "{message}");
+    assert!(message.contains("properties/b/disallow"), "{message}");
+    // Both failures were collected in the same pass, not just the first one.
+    assert_eq!(message.lines().count(), 2, "{message}");
+}
+
+// #chunk0-2: `prefixItems` (2020-12) and `items`/`additionalItems`
+// (2019-09 and earlier) normalize to the same `schemars` shape.
+#[test]
+fn prefix_items_and_legacy_additional_items_normalize_the_same_as_tuple_items() {
+    let prefix_items_form = convert(
+        r#"{
+            "prefixItems": [{"type": "string"}, {"type": "number"}],
+            "additionalItems": {"type": "boolean"}
+        }"#,
+    )
+    .unwrap();
+    let legacy_items_form = convert(
+        r#"{
+            "items": [{"type": "string"}, {"type": "number"}],
+            "additionalItems": {"type": "boolean"}
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        prefix_items_form.schema.array, legacy_items_form.schema.array,
+        "prefixItems+additionalItems should convert to the same tuple-with-rest shape as items+additionalItems"
+    );
+}
+
+#[test]
+fn prefix_items_rejects_an_ambiguous_rest_schema() {
+    let err = convert(
+        r#"{
+            "prefixItems": [{"type": "string"}],
+            "items": {"type": "number"},
+            "additionalItems": {"type": "boolean"}
+        }"#,
+    )
+    .expect_err("prefixItems paired with both a single-schema items and additionalItems is ambiguous");
+
+    assert!(err.to_string().contains("additionalItems"), "{err}");
+}
+
+// #chunk0-3: `allOf` flattening tightens constraints onto the parent
+// instead of silently dropping members it can't fully absorb.
+#[test]
+fn merge_all_of_tightens_array_bounds_onto_parent() {
+    let parsed: RootSchema = serde_json::from_str(
+        r#"{
+            "type": "array",
+            "minItems": 1,
+            "allOf": [
+                {"minItems": 3},
+                {"maxItems": 10}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let merged = parsed.schema.merge_all_of();
+
+    let all_of = merged.subschemas.as_ref().and_then(|s| s.all_of.as_ref());
+    assert!(all_of.is_none(), "fully-absorbed allOf should be removed, got {all_of:?}");
+    let array = merged.array.unwrap();
+    assert_eq!(array.min_items, Some(3), "tighter (larger) minItems should win");
+    assert_eq!(array.max_items, Some(10));
+}
+
+#[test]
+fn merge_all_of_absorbs_an_array_members_items_constraint() {
+    let parsed: RootSchema = serde_json::from_str(
+        r#"{
+            "type": "array",
+            "allOf": [
+                {"items": {"type": "string"}}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let merged = parsed.schema.merge_all_of();
+
+    let all_of = merged.subschemas.as_ref().and_then(|s| s.all_of.as_ref());
+    assert!(all_of.is_none(), "fully-absorbed allOf should be removed, got {all_of:?}");
+    let items = merged.array.unwrap().items.expect("the member's \"items\" constraint must survive the merge");
+    assert!(matches!(items, schemars::schema::SingleOrVec::Single(_)));
+}
+
+#[test]
+fn merge_all_of_leaves_a_conflicting_array_member_in_place() {
+    let parsed: RootSchema = serde_json::from_str(
+        r#"{
+            "type": "array",
+            "items": {"type": "number"},
+            "allOf": [
+                {"items": {"type": "string"}}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let merged = parsed.schema.merge_all_of();
+
+    let all_of = merged
+        .subschemas
+        .unwrap()
+        .all_of
+        .expect("a member whose \"items\" conflicts with the parent's must not be dropped");
+    assert_eq!(all_of.len(), 1);
+    let items = merged.array.unwrap().items.expect("the parent's own \"items\" constraint must survive");
+    let items = match items {
+        schemars::schema::SingleOrVec::Single(s) => match *s {
+            parse_jsonschema::Schema::Object(o) => o,
+            parse_jsonschema::Schema::Bool(_) => unreachable!(),
+        },
+        schemars::schema::SingleOrVec::Vec(_) => unreachable!(),
+    };
+    assert_eq!(
+        items.instance_type,
+        Some(schemars::schema::SingleOrVec::Single(Box::new(schemars::schema::InstanceType::Number))),
+        "and must still be \"number\", not the conflicting member's \"string\""
+    );
+}
+
+#[test]
+fn merge_all_of_leaves_unabsorbable_members_in_place() {
+    let parsed: RootSchema = serde_json::from_str(
+        r#"{
+            "minItems": 1,
+            "allOf": [
+                {"anyOf": [{"type": "string"}, {"type": "number"}]}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let merged = parsed.schema.merge_all_of();
+
+    let all_of = merged.subschemas.unwrap().all_of.expect("member with anyOf must not be dropped");
+    assert_eq!(all_of.len(), 1);
+}
+
+// #chunk0-4: `$ref` resolution detects cycles reached through a child of
+// the target, and dangling references, instead of recursing forever.
+#[test]
+fn resolve_references_inlines_a_valid_ref() {
+    let mut parsed: RootSchema = serde_json::from_str(
+        r##"{
+            "properties": {
+                "a": {"$ref": "#/definitions/Named"}
+            },
+            "definitions": {
+                "Named": {"type": "string"}
+            }
+        }"##,
+    )
+    .unwrap();
+
+    parsed.resolve_references(RefResolution::Inline).unwrap();
+
+    let a = parsed.schema.object.unwrap().properties.get("a").unwrap().clone();
+    assert!(matches!(a, parse_jsonschema::Schema::Object(_)), "a should still be an inline schema, not a $ref");
+    let a = match a {
+        parse_jsonschema::Schema::Object(o) => o,
+        parse_jsonschema::Schema::Bool(_) => unreachable!(),
+    };
+    assert!(a.reference.is_none(), "the $ref should have been replaced by its target");
+    assert_eq!(a.instance_type, Some(schemars::schema::SingleOrVec::Single(Box::new(schemars::schema::InstanceType::String))));
+}
+
+#[test]
+fn resolve_references_detects_a_cycle_reached_through_a_child() {
+    // A linked-list-style self-reference reached through `properties.next`,
+    // not a direct `$ref` chain.
+    let mut parsed: RootSchema = serde_json::from_str(
+        r##"{
+            "$ref": "#/definitions/Node",
+            "definitions": {
+                "Node": {
+                    "type": "object",
+                    "properties": {
+                        "next": {"$ref": "#/definitions/Node"}
+                    }
+                }
+            }
+        }"##,
+    )
+    .unwrap();
+
+    let err = parsed
+        .resolve_references(RefResolution::Inline)
+        .expect_err("a self-reference through a child must be caught, not recursed forever");
+
+    assert!(err.to_string().contains("reference cycle detected"), "{err}");
+}
+
+#[test]
+fn resolve_references_detects_a_dangling_ref() {
+    let mut parsed: RootSchema = serde_json::from_str(
+        r##"{
+            "properties": {
+                "a": {"$ref": "#/definitions/Missing"}
+            }
+        }"##,
+    )
+    .unwrap();
+
+    let err = parsed
+        .resolve_references(RefResolution::Validate)
+        .expect_err("a $ref naming a nonexistent definition should be reported");
+
+    assert!(err.to_string().contains("does not match any definition"), "{err}");
+}